@@ -0,0 +1,160 @@
+use std::str::FromStr;
+
+use bdk_wallet::bitcoin::bip32::Xpriv;
+use bdk_wallet::bitcoin::hashes::Hash;
+use bdk_wallet::bitcoin::key::Secp256k1;
+
+use bdk_wallet::bitcoin::{self, Amount, FeeRate, OutPoint, Transaction, TxIn, TxOut, Txid};
+
+use bdk_wallet::miniscript::psbt::PsbtExt;
+use bdk_wallet::miniscript::DescriptorPublicKey;
+use bdk_wallet::shared_output::SharedOutput;
+use bdk_wallet::vault::UnvaultTimelock;
+use bitcoin::{absolute, transaction, Address, Network};
+
+// A shared 2-of-2 output used for a collaborative custody / swap flow: a
+// `lock` transaction funds it, a `redeem` transaction spends it
+// cooperatively, and a `refund` transaction lets party A reclaim the funds
+// unilaterally, via a dedicated refund key, if party B disappears before a
+// timelock matures.
+fn main() {
+    let party_a_tprv = "tprv8ZgxMBicQKsPekKEvzvCnK7qe5r6ausugHDyrPeX9TLQ4oADSYLWtA4m3XsEMmUZEbVaeJtuZimakomLkecLTMwerVJKpAZFtXoo7DYb84B";
+    let party_a_pk = "033b4ac89f5d83de29af72d8b99963c4dbd416fa7c8a8aee6b4761f8f85e588f80";
+    let party_b_tprv = "tprv8ZgxMBicQKsPdKyH699thnjrFcmJMrUUoaNZvHYxxqvhySPhAYZpmxtR39u5QAYnhtYSfMBuBBH6pGuSgmoK3NpfNDU3RAbrVpcbpLmz5ot";
+    let party_b_pk = "02e7c62fd3a65abdc7ff233fba5637f89c9eaba7fe6baaf15ca99d81e0f5145bf8";
+    // `Concrete::compile` rejects a policy that repeats a leaf key, so party A
+    // needs a refund key distinct from the one it uses in the 2-of-2 redeem
+    // path (e.g. a different address of the same wallet).
+    let refund_tprv = "tprv8ZgxMBicQKsPeX3aFQNrVf2xfxRHhXMPCVTLvf14UpGqHe8Pw7LzBeGUCq3xoSNpUY43Kgu3rnwi21yNgHYdkjCspdYdkyqq7DBEhxGK9uf";
+    let refund_pk = "022400d18c94f53ccb70d60dab2c798df9173d346de78e0d13eadd48e7d98d993f";
+
+    let party_a = DescriptorPublicKey::from_str(party_a_pk).unwrap();
+    let party_b = DescriptorPublicKey::from_str(party_b_pk).unwrap();
+    let refund_key = DescriptorPublicKey::from_str(refund_pk).unwrap();
+    let refund_height = 1311208;
+    let shared = SharedOutput::new(
+        party_a,
+        party_b,
+        refund_key,
+        UnvaultTimelock::Absolute(refund_height),
+    )
+    .expect("couldn't create shared output");
+    println!("The shared output descriptor is: {}\n", shared.descriptor());
+
+    // Party A funds the shared address from an output of their own wallet.
+    let lock_address = shared.lock_address(Network::Testnet);
+    println!("The shared lock address is {lock_address}");
+    let party_a_address = Address::from_str("tb1q9pa3khpnkg6pqczly60fjz6c7wrgn06jyuzpp9")
+        .unwrap()
+        .assume_checked();
+    let funding_tx = funding_transaction(party_a_address.clone());
+    let (funding_previous_output, funding_witness_utxo) =
+        get_vout(&funding_tx, &party_a_address.script_pubkey());
+
+    let lock_psbt = shared.build_lock(
+        funding_previous_output,
+        funding_witness_utxo.clone(),
+        funding_witness_utxo.value,
+    );
+    println!(
+        "Built the lock psbt funding the shared output: {:#?}",
+        lock_psbt.unsigned_tx
+    );
+
+    // The lock transaction's output is what redeem/refund actually spend.
+    let (shared_previous_output, shared_witness_utxo) = (
+        OutPoint::new(lock_psbt.unsigned_tx.compute_txid(), 0),
+        lock_psbt.unsigned_tx.output[0].clone(),
+    );
+
+    let fee_rate = FeeRate::from_sat_per_vb(2).expect("valid fee rate");
+    let secp = Secp256k1::new();
+    let party_a_xpriv = Xpriv::from_str(party_a_tprv).unwrap();
+    let party_b_xpriv = Xpriv::from_str(party_b_tprv).unwrap();
+    let refund_xpriv = Xpriv::from_str(refund_tprv).unwrap();
+
+    // Cooperative redeem, once both parties are ready to settle.
+    let redeem_recipient = Address::from_str("tb1qw2c3lxufxqe2x9s4rdzh65tpf4d7fssjgh8nv6")
+        .unwrap()
+        .assume_checked()
+        .script_pubkey();
+    let mut redeem_psbt = shared
+        .build_redeem(
+            shared_previous_output,
+            shared_witness_utxo.clone(),
+            redeem_recipient,
+            fee_rate,
+        )
+        .expect("couldn't build redeem psbt");
+
+    // The `thresh(2,pk(a),pk(b))` redeem path needs both parties' signatures.
+    redeem_psbt
+        .sign(&party_a_xpriv, &secp)
+        .expect("failed to sign redeem psbt as party a");
+    redeem_psbt
+        .sign(&party_b_xpriv, &secp)
+        .expect("failed to sign redeem psbt as party b");
+    redeem_psbt
+        .finalize_mut(&secp)
+        .expect("problem finalizing redeem psbt");
+    let redeem_tx = redeem_psbt
+        .extract_tx()
+        .expect("failed to extract redeem tx");
+    println!("Built and finalized the redeem tx: {:#?}\n", redeem_tx);
+
+    // Or, if party B vanishes, the refund key can reclaim the funds back to
+    // party A once the chain reaches `refund_height`.
+    let refund_recipient = Address::from_str("tb1q9pa3khpnkg6pqczly60fjz6c7wrgn06jyuzpp9")
+        .unwrap()
+        .assume_checked()
+        .script_pubkey();
+    let mut refund_psbt = shared
+        .build_refund(
+            shared_previous_output,
+            shared_witness_utxo,
+            refund_recipient,
+            fee_rate,
+        )
+        .expect("couldn't build refund psbt");
+
+    // The refund path only needs the refund key's signature.
+    refund_psbt
+        .sign(&refund_xpriv, &secp)
+        .expect("failed to sign refund psbt");
+    refund_psbt
+        .finalize_mut(&secp)
+        .expect("problem finalizing refund psbt");
+    let refund_tx = refund_psbt
+        .extract_tx()
+        .expect("failed to extract refund tx");
+    println!("Built and finalized the refund tx: {:#?}", refund_tx);
+}
+
+fn get_vout(tx: &Transaction, spk: &bitcoin::Script) -> (OutPoint, TxOut) {
+    for (i, txout) in tx.clone().output.into_iter().enumerate() {
+        if spk == &txout.script_pubkey {
+            return (OutPoint::new(tx.compute_txid(), i as u32), txout);
+        }
+    }
+    panic!("Only call get vout on functions which have the expected outpoint");
+}
+
+fn funding_transaction(receive_address: Address) -> bitcoin::Transaction {
+    Transaction {
+        version: transaction::Version::ONE,
+        lock_time: absolute::LockTime::ZERO,
+        input: vec![TxIn {
+            previous_output: OutPoint {
+                txid: Txid::all_zeros(),
+                vout: 0,
+            },
+            script_sig: Default::default(),
+            sequence: Default::default(),
+            witness: Default::default(),
+        }],
+        output: vec![TxOut {
+            value: Amount::from_sat(76_000),
+            script_pubkey: receive_address.script_pubkey(),
+        }],
+    }
+}
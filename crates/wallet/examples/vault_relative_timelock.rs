@@ -0,0 +1,103 @@
+use std::str::FromStr;
+
+use bdk_wallet::bitcoin::bip32::Xpriv;
+use bdk_wallet::bitcoin::hashes::Hash;
+use bdk_wallet::bitcoin::key::Secp256k1;
+
+use bdk_wallet::bitcoin::{self, Amount, FeeRate, OutPoint, Sequence, Transaction, TxIn, TxOut, Txid};
+
+use bdk_wallet::miniscript::psbt::PsbtExt;
+use bdk_wallet::miniscript::DescriptorPublicKey;
+use bdk_wallet::vault::{UnvaultTimelock, Vault};
+use bitcoin::{absolute, transaction, Address, Network};
+
+// Using the `Vault` API with a CSV-based unvault path: instead of an absolute
+// block height, the unvault key becomes spendable a fixed number of blocks
+// *after* the deposit transaction confirms (BIP68 relative timelock).
+fn main() {
+    let unvault_tprv = "tprv8ZgxMBicQKsPdKyH699thnjrFcmJMrUUoaNZvHYxxqvhySPhAYZpmxtR39u5QAYnhtYSfMBuBBH6pGuSgmoK3NpfNDU3RAbrVpcbpLmz5ot";
+    let unvault_pk = "02e7c62fd3a65abdc7ff233fba5637f89c9eaba7fe6baaf15ca99d81e0f5145bf8";
+
+    let emergency_pk = "033b4ac89f5d83de29af72d8b99963c4dbd416fa7c8a8aee6b4761f8f85e588f80";
+
+    // Spendable by the unvault key 144 blocks (roughly one day) after the
+    // deposit transaction confirms.
+    let relative_delay = Sequence::from_height(144);
+
+    let emergency_key = DescriptorPublicKey::from_str(emergency_pk).unwrap();
+    let unvault_key = DescriptorPublicKey::from_str(unvault_pk).unwrap();
+    let vault = Vault::new(
+        emergency_key,
+        unvault_key,
+        UnvaultTimelock::Relative(relative_delay),
+    )
+    .expect("couldn't create vault");
+    println!("The vault descriptor is: {}\n", vault.descriptor());
+
+    let vault_address = vault.deposit_address(Network::Testnet);
+    println!("The vault address is {:?}", vault_address);
+
+    let deposit_tx = deposit_transaction(vault_address);
+    let vault_spk = vault
+        .descriptor()
+        .at_derivation_index(0)
+        .expect("vault descriptor has no wildcard, index is unused")
+        .script_pubkey();
+    let (previous_output, witness_utxo) = get_vout(&deposit_tx, &vault_spk);
+
+    // Spend via the unvault path. `spend_unvault` sets the input's sequence to
+    // the BIP68-encoded relative value and keeps the transaction at version 2,
+    // which are both required for the CSV delay to be consensus-enforced.
+    let recipient_script = Address::from_str("tb1qw2c3lxufxqe2x9s4rdzh65tpf4d7fssjgh8nv6")
+        .unwrap()
+        .assume_checked()
+        .script_pubkey();
+    let fee_rate = FeeRate::from_sat_per_vb(2).expect("valid fee rate");
+    let mut psbt = vault
+        .spend_unvault(previous_output, witness_utxo, recipient_script, fee_rate)
+        .expect("couldn't build unvault spend psbt");
+
+    let secp = Secp256k1::new();
+    let unvault_xpriv = Xpriv::from_str(unvault_tprv).unwrap();
+    psbt.sign(&unvault_xpriv, &secp)
+        .expect("failed to sign unvault psbt");
+
+    // Finalizing only checks the transaction's own sequence and version, so
+    // it succeeds right away. The resulting transaction can't be mined until
+    // 144 blocks after the deposit transaction confirms; a real wallet would
+    // wait for that before broadcasting.
+    psbt.finalize_mut(&secp)
+        .expect("problem finalizing unvault psbt");
+    println!("Finalized unvault spend psbt using a relative timelock");
+
+    let _my_unvault_tx = psbt.extract_tx().expect("failed to extract unvault tx");
+}
+
+fn get_vout(tx: &Transaction, spk: &bitcoin::Script) -> (OutPoint, TxOut) {
+    for (i, txout) in tx.clone().output.into_iter().enumerate() {
+        if spk == &txout.script_pubkey {
+            return (OutPoint::new(tx.compute_txid(), i as u32), txout);
+        }
+    }
+    panic!("Only call get vout on functions which have the expected outpoint");
+}
+
+fn deposit_transaction(receive_address: Address) -> bitcoin::Transaction {
+    Transaction {
+        version: transaction::Version::ONE,
+        lock_time: absolute::LockTime::ZERO,
+        input: vec![TxIn {
+            previous_output: OutPoint {
+                txid: Txid::all_zeros(),
+                vout: 0,
+            },
+            script_sig: Default::default(),
+            sequence: Default::default(),
+            witness: Default::default(),
+        }],
+        output: vec![TxOut {
+            value: Amount::from_sat(76_000),
+            script_pubkey: receive_address.script_pubkey(),
+        }],
+    }
+}
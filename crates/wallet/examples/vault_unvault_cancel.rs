@@ -0,0 +1,149 @@
+use std::str::FromStr;
+
+use bdk_wallet::bitcoin::bip32::Xpriv;
+use bdk_wallet::bitcoin::hashes::Hash;
+use bdk_wallet::bitcoin::key::Secp256k1;
+
+use bdk_wallet::bitcoin::{
+    self, Amount, FeeRate, OutPoint, Sequence, Transaction, TxIn, TxOut, Txid,
+};
+
+use bdk_wallet::miniscript::psbt::PsbtExt;
+use bdk_wallet::miniscript::DescriptorPublicKey;
+use bdk_wallet::unvault_chain::UnvaultChain;
+use bdk_wallet::vault::{UnvaultTimelock, Vault};
+use bitcoin::{absolute, transaction, Address, Network};
+
+// Modeling the unvault/cancel pattern as a chain of linked transactions: the
+// deposit is first spent into an `unvault_tx` with a CSV-delayed main output
+// plus a CPFP anchor output, and a watcher can spend that main output via a
+// `cancel_tx` at any time before the delay matures, reverting the unvault.
+fn main() {
+    let emergency_pk = "033b4ac89f5d83de29af72d8b99963c4dbd416fa7c8a8aee6b4761f8f85e588f80";
+    let unvault_tprv = "tprv8ZgxMBicQKsPdKyH699thnjrFcmJMrUUoaNZvHYxxqvhySPhAYZpmxtR39u5QAYnhtYSfMBuBBH6pGuSgmoK3NpfNDU3RAbrVpcbpLmz5ot";
+    let unvault_pk = "02e7c62fd3a65abdc7ff233fba5637f89c9eaba7fe6baaf15ca99d81e0f5145bf8";
+    let cancel_tprv = "tprv8ZgxMBicQKsPfGdzrT8uKD49kpECq426eCyMxousFR8hwBad2VTQXB5F5HcN2TKytW4JQUWXQ2StCPBzuechVFDFsdef8W6vEhXD2fPLYwX";
+    let cancel_pk = "03cac27d2830ccf270832b2f3e3c16429396b9a4e1bee0bae2ae34b93d7a5e06cd";
+    let cpfp_pk = "0388bb0fbeeb85c9df3708bf2e1f5f48b215fbafbb839676bdc7c35462d2ab98a7";
+
+    let emergency_key = DescriptorPublicKey::from_str(emergency_pk).unwrap();
+    let unvault_key = DescriptorPublicKey::from_str(unvault_pk).unwrap();
+    // `UnvaultChain::build_unvault` requires a relative vault timelock: the
+    // deposit-spending transaction it builds must be broadcastable as soon
+    // as the deposit has a single confirmation, with the CSV delay living
+    // only on the chain's own unvault output (see below).
+    let vault = Vault::new(
+        emergency_key,
+        unvault_key.clone(),
+        UnvaultTimelock::Relative(Sequence::from_height(1)),
+    )
+    .expect("couldn't create vault");
+
+    let vault_address = vault.deposit_address(Network::Testnet);
+    let deposit_tx = deposit_transaction(vault_address);
+    let vault_spk = vault
+        .descriptor()
+        .at_derivation_index(0)
+        .expect("vault descriptor has no wildcard, index is unused")
+        .script_pubkey();
+    let (deposit_previous_output, deposit_witness_utxo) = get_vout(&deposit_tx, &vault_spk);
+
+    // 144 blocks (roughly one day) after the unvault transaction confirms,
+    // the unvault key alone is enough to spend the main output.
+    let csv_delay = Sequence::from_height(144);
+    let cancel_key = DescriptorPublicKey::from_str(cancel_pk).unwrap();
+    let cpfp_key = DescriptorPublicKey::from_str(cpfp_pk).unwrap();
+    let chain = UnvaultChain::new(unvault_key, cancel_key, csv_delay, cpfp_key)
+        .expect("couldn't create unvault chain");
+    println!(
+        "The unvault descriptor is: {}\n",
+        chain.unvault_descriptor()
+    );
+    println!(
+        "The CPFP anchor descriptor is: {}\n",
+        chain.cpfp_descriptor()
+    );
+
+    let secp = Secp256k1::new();
+
+    let fee_rate = FeeRate::from_sat_per_vb(2).expect("valid fee rate");
+    let mut unvault_psbt = chain
+        .build_unvault(
+            &vault,
+            deposit_previous_output,
+            deposit_witness_utxo,
+            fee_rate,
+        )
+        .expect("couldn't build unvault psbt");
+
+    // `build_unvault` spends the deposit via the vault's unvault path, so it's
+    // the unvault key (not the chain's cancel key) that signs this stage.
+    let unvault_xpriv = Xpriv::from_str(unvault_tprv).unwrap();
+    unvault_psbt
+        .sign(&unvault_xpriv, &secp)
+        .expect("failed to sign unvault psbt");
+    unvault_psbt
+        .finalize_mut(&secp)
+        .expect("problem finalizing unvault psbt");
+    let unvault_tx = unvault_psbt
+        .extract_tx()
+        .expect("failed to extract unvault tx");
+    println!(
+        "Unvault tx has a main output and a {} CPFP anchor output",
+        bdk_wallet::unvault_chain::ANCHOR_VALUE
+    );
+
+    // A watcher notices the unvault and reverts it before the CSV delay
+    // matures, spending the main output back to the vault's deposit address.
+    let fresh_vault_address = vault.deposit_address(Network::Testnet);
+    let mut cancel_psbt = chain
+        .build_cancel(&unvault_tx, fresh_vault_address.script_pubkey(), fee_rate)
+        .expect("couldn't build cancel psbt");
+
+    // The cancel spend is authorized by the chain's cancel key, immediately
+    // (no need to wait out the CSV delay).
+    let cancel_xpriv = Xpriv::from_str(cancel_tprv).unwrap();
+    cancel_psbt
+        .sign(&cancel_xpriv, &secp)
+        .expect("failed to sign cancel psbt");
+    cancel_psbt
+        .finalize_mut(&secp)
+        .expect("problem finalizing cancel psbt");
+    let cancel_tx = cancel_psbt
+        .extract_tx()
+        .expect("failed to extract cancel tx");
+
+    println!(
+        "Built and finalized a cancel tx spending the unvault main output back into the vault: {:#?}",
+        cancel_tx
+    );
+}
+
+fn get_vout(tx: &Transaction, spk: &bitcoin::Script) -> (OutPoint, TxOut) {
+    for (i, txout) in tx.clone().output.into_iter().enumerate() {
+        if spk == &txout.script_pubkey {
+            return (OutPoint::new(tx.compute_txid(), i as u32), txout);
+        }
+    }
+    panic!("Only call get vout on functions which have the expected outpoint");
+}
+
+fn deposit_transaction(receive_address: Address) -> bitcoin::Transaction {
+    Transaction {
+        version: transaction::Version::ONE,
+        lock_time: absolute::LockTime::ZERO,
+        input: vec![TxIn {
+            previous_output: OutPoint {
+                txid: Txid::all_zeros(),
+                vout: 0,
+            },
+            script_sig: Default::default(),
+            sequence: Default::default(),
+            witness: Default::default(),
+        }],
+        output: vec![TxOut {
+            value: Amount::from_sat(76_000),
+            script_pubkey: receive_address.script_pubkey(),
+        }],
+    }
+}
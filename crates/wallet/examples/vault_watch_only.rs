@@ -0,0 +1,101 @@
+use std::str::FromStr;
+
+use bdk_wallet::bitcoin::bip32::Xpriv;
+use bdk_wallet::bitcoin::hashes::Hash;
+use bdk_wallet::bitcoin::key::Secp256k1;
+
+use bdk_wallet::bitcoin::{self, Amount, FeeRate, OutPoint, Transaction, TxIn, TxOut, Txid};
+
+use bdk_wallet::miniscript::DescriptorPublicKey;
+use bdk_wallet::vault::{self, UnvaultTimelock, Vault};
+use bitcoin::{absolute, transaction, Address, Network};
+
+// Using the `Vault` API across a watch-only / offline-signer machine boundary,
+// the way a real cold-storage setup would: the watch-only side only ever
+// holds `DescriptorPublicKey`s and builds the unsigned spend PSBT; the offline
+// side holds the `Xpriv` and signs a base64 copy of it without knowing
+// anything about the vault's policy.
+fn main() {
+    let emergency_tprv = "tprv8ZgxMBicQKsPekKEvzvCnK7qe5r6ausugHDyrPeX9TLQ4oADSYLWtA4m3XsEMmUZEbVaeJtuZimakomLkecLTMwerVJKpAZFtXoo7DYb84B";
+    let emergency_pk = "033b4ac89f5d83de29af72d8b99963c4dbd416fa7c8a8aee6b4761f8f85e588f80";
+    let unvault_pk = "02e7c62fd3a65abdc7ff233fba5637f89c9eaba7fe6baaf15ca99d81e0f5145bf8";
+    let after = 1311208;
+
+    // --- Watch-only side: only ever sees public keys. ---
+
+    let emergency_key = DescriptorPublicKey::from_str(emergency_pk).unwrap();
+    let unvault_key = DescriptorPublicKey::from_str(unvault_pk).unwrap();
+    let vault = Vault::new(emergency_key, unvault_key, UnvaultTimelock::Absolute(after))
+        .expect("couldn't create vault");
+
+    let vault_address = vault.deposit_address(Network::Testnet);
+    let deposit_tx = deposit_transaction(vault_address);
+    let vault_spk = vault
+        .descriptor()
+        .at_derivation_index(0)
+        .expect("vault descriptor has no wildcard, index is unused")
+        .script_pubkey();
+    let (previous_output, witness_utxo) = get_vout(&deposit_tx, &vault_spk);
+
+    let recipient_script = Address::from_str("tb1qw2c3lxufxqe2x9s4rdzh65tpf4d7fssjgh8nv6")
+        .unwrap()
+        .assume_checked()
+        .script_pubkey();
+    let fee_rate = FeeRate::from_sat_per_vb(2).expect("valid fee rate");
+    let unsigned_psbt = vault
+        .spend_emergency(previous_output, witness_utxo, recipient_script, fee_rate)
+        .expect("couldn't build emergency spend psbt");
+
+    // Serialize the PSBT to its BIP174 base64 text encoding to hand it to the
+    // offline signer, e.g. over a QR code or a USB drive.
+    let transport = vault::to_base64(&unsigned_psbt);
+    println!("Unsigned PSBT for the offline signer:\n{transport}\n");
+
+    // --- Offline side: only ever sees the Xpriv, never the vault's policy. ---
+
+    let mut offline_psbt = vault::from_base64(&transport).expect("couldn't parse psbt");
+    let secp = Secp256k1::new();
+    let emergency_key = Xpriv::from_str(emergency_tprv).expect("couldn't parse xpriv");
+    offline_psbt
+        .sign(&emergency_key, &secp)
+        .expect("failed to sign emergency spend psbt");
+    let signed_transport = vault::to_base64(&offline_psbt);
+
+    // --- Watch-only side: combines the signature back in and finalizes. ---
+
+    let signed_psbt = vault::from_base64(&signed_transport).expect("couldn't parse psbt");
+    let finalized = vault::combine_and_finalize(unsigned_psbt, signed_psbt, &secp)
+        .expect("couldn't combine and finalize psbt");
+
+    let _tx = finalized.extract_tx().expect("failed to extract tx");
+    println!("Finalized the emergency spend entirely from a base64 PSBT round-trip");
+}
+
+fn get_vout(tx: &Transaction, spk: &bitcoin::Script) -> (OutPoint, TxOut) {
+    for (i, txout) in tx.clone().output.into_iter().enumerate() {
+        if spk == &txout.script_pubkey {
+            return (OutPoint::new(tx.compute_txid(), i as u32), txout);
+        }
+    }
+    panic!("Only call get vout on functions which have the expected outpoint");
+}
+
+fn deposit_transaction(receive_address: Address) -> bitcoin::Transaction {
+    Transaction {
+        version: transaction::Version::ONE,
+        lock_time: absolute::LockTime::ZERO,
+        input: vec![TxIn {
+            previous_output: OutPoint {
+                txid: Txid::all_zeros(),
+                vout: 0,
+            },
+            script_sig: Default::default(),
+            sequence: Default::default(),
+            witness: Default::default(),
+        }],
+        output: vec![TxOut {
+            value: Amount::from_sat(76_000),
+            script_pubkey: receive_address.script_pubkey(),
+        }],
+    }
+}
@@ -0,0 +1,19 @@
+// Bitcoin Dev Kit
+// Written in 2020 by Alekos Filini <alekos.filini@gmail.com>
+//
+// Copyright (c) 2020-2024 Bitcoin Dev Kit Developers
+//
+// This file is licensed under the Apache License, Version 2.0 <LICENSE-APACHE
+// or http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your option.
+// You may not use this file except in accordance with one or both of these
+// licenses.
+
+//! A modern, lightweight, descriptor-based wallet library written in Rust.
+
+pub use bitcoin;
+pub use miniscript;
+
+pub mod shared_output;
+pub mod unvault_chain;
+pub mod vault;
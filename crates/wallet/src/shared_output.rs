@@ -0,0 +1,254 @@
+// Bitcoin Dev Kit
+// Written in 2020 by Alekos Filini <alekos.filini@gmail.com>
+//
+// Copyright (c) 2020-2024 Bitcoin Dev Kit Developers
+//
+// This file is licensed under the Apache License, Version 2.0 <LICENSE-APACHE
+// or http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your option.
+// You may not use this file except in accordance with one or both of these
+// licenses.
+
+//! A shared `wsh(thresh(2,a,b))`-style output for collaborative custody /
+//! swap flows, with a `lock` transaction that funds it, a `redeem`
+//! transaction that spends it cooperatively, and a `refund` transaction that
+//! lets the depositing party reclaim the funds unilaterally once a timelock
+//! matures.
+
+use std::str::FromStr;
+
+use bitcoin::{
+    absolute, psbt, relative, Address, Amount, FeeRate, Network, OutPoint, Psbt, ScriptBuf,
+    Sequence, TxIn, TxOut,
+};
+
+use miniscript::plan::Assets;
+use miniscript::policy::Concrete;
+use miniscript::{Descriptor, DescriptorPublicKey};
+
+use crate::vault::{blank_transaction, output_value_for_fee_rate, UnvaultTimelock, VaultError};
+
+/// A shared 2-of-2 output between `party_a` and `party_b`, refundable to a
+/// separate `refund_key` once `refund_timelock` matures.
+///
+/// The underlying policy is
+/// `or(thresh(2,pk(party_a),pk(party_b)),and(pk(refund_key),timelock))`:
+/// either party can cooperate with [`SharedOutput::build_redeem`], or
+/// whoever holds `refund_key` can reclaim the funds with
+/// [`SharedOutput::build_refund`] if the counterparty disappears.
+///
+/// `refund_key` must be a key distinct from both `party_a` and `party_b`:
+/// `Concrete::compile` rejects any policy that repeats a leaf key, so the
+/// depositing party needs a dedicated refund key (e.g. a different address
+/// of the same wallet) rather than reusing their `party_a`/`party_b` key.
+#[derive(Debug, Clone)]
+pub struct SharedOutput {
+    party_a: DescriptorPublicKey,
+    party_b: DescriptorPublicKey,
+    refund_key: DescriptorPublicKey,
+    refund_timelock: UnvaultTimelock,
+    descriptor: Descriptor<DescriptorPublicKey>,
+}
+
+impl SharedOutput {
+    /// Build the shared output descriptor from the two parties' keys, the
+    /// `refund_key` (distinct from both) that can unilaterally reclaim the
+    /// funds, and the timelock that gates that refund path.
+    pub fn new(
+        party_a: DescriptorPublicKey,
+        party_b: DescriptorPublicKey,
+        refund_key: DescriptorPublicKey,
+        refund_timelock: UnvaultTimelock,
+    ) -> Result<Self, VaultError> {
+        if let UnvaultTimelock::Absolute(height) = refund_timelock {
+            absolute::LockTime::from_height(height).map_err(VaultError::InvalidTimelockHeight)?;
+        }
+
+        let policy_str = format!(
+            "or(thresh(2,pk({party_a}),pk({party_b})),and(pk({refund_key}),{refund_timelock}))"
+        );
+        let policy = Concrete::<DescriptorPublicKey>::from_str(&policy_str)
+            .map_err(VaultError::PolicyParse)?;
+        let compiled = policy.compile().map_err(VaultError::Policy)?;
+        let descriptor = Descriptor::new_wsh(compiled).map_err(VaultError::Descriptor)?;
+
+        Ok(Self {
+            party_a,
+            party_b,
+            refund_key,
+            refund_timelock,
+            descriptor,
+        })
+    }
+
+    /// The underlying `wsh(or(multi(2,a,b),and(pk(refund_key),...)))` descriptor.
+    pub fn descriptor(&self) -> &Descriptor<DescriptorPublicKey> {
+        &self.descriptor
+    }
+
+    /// The address the lock transaction should pay.
+    pub fn lock_address(&self, network: Network) -> Address {
+        self.descriptor
+            .at_derivation_index(0)
+            .expect("shared output descriptor has no wildcard, index is unused")
+            .address(network)
+            .expect("wsh descriptors always have an address")
+    }
+
+    /// Build the PSBT that funds the shared 2-of-2 output, spending
+    /// `previous_output` (with `witness_utxo`) for `value`.
+    ///
+    /// Unlike [`SharedOutput::build_redeem`]/[`SharedOutput::build_refund`],
+    /// this input isn't controlled by this descriptor — it belongs to
+    /// whichever wallet is funding the lock — so there's no plan to attach
+    /// and the caller's own wallet is responsible for signing it.
+    pub fn build_lock(
+        &self,
+        previous_output: OutPoint,
+        witness_utxo: TxOut,
+        value: Amount,
+    ) -> Psbt {
+        let recipient = TxOut {
+            script_pubkey: self
+                .descriptor
+                .at_derivation_index(0)
+                .expect("shared output descriptor has no wildcard, index is unused")
+                .script_pubkey(),
+            value,
+        };
+
+        let tx = blank_transaction(absolute::LockTime::ZERO);
+        let mut psbt = Psbt::from_unsigned_tx(tx).expect("tx has no signatures yet");
+
+        psbt.unsigned_tx.input.push(TxIn {
+            previous_output,
+            ..Default::default()
+        });
+        psbt.unsigned_tx.output.push(recipient);
+
+        psbt.inputs.push(psbt::Input {
+            witness_utxo: Some(witness_utxo),
+            ..Default::default()
+        });
+        psbt.outputs.push(psbt::Output::default());
+
+        psbt
+    }
+
+    fn plan_for(&self, assets: &Assets) -> Result<miniscript::plan::Plan, VaultError> {
+        self.descriptor
+            .at_derivation_index(0)
+            .expect("shared output descriptor has no wildcard, index is unused")
+            .plan(assets)
+            .map_err(|_| VaultError::Plan)
+    }
+
+    /// Build a ready-to-sign PSBT cooperatively spending `previous_output`
+    /// (with `witness_utxo`) to `recipient_script`, paying `fee_rate`.
+    pub fn build_redeem(
+        &self,
+        previous_output: OutPoint,
+        witness_utxo: TxOut,
+        recipient_script: ScriptBuf,
+        fee_rate: FeeRate,
+    ) -> Result<Psbt, VaultError> {
+        let assets = Assets::new()
+            .add(self.party_a.clone())
+            .add(self.party_b.clone());
+        let plan = self.plan_for(&assets)?;
+        let sequence = Sequence::ENABLE_RBF_NO_LOCKTIME;
+
+        let recipient = output_value_for_fee_rate(
+            witness_utxo.value,
+            previous_output,
+            sequence,
+            recipient_script,
+            &plan,
+            fee_rate,
+        )?;
+
+        let tx = blank_transaction(absolute::LockTime::ZERO);
+        let mut psbt = Psbt::from_unsigned_tx(tx).expect("tx has no signatures yet");
+
+        psbt.unsigned_tx.input.push(TxIn {
+            previous_output,
+            sequence,
+            ..Default::default()
+        });
+        psbt.unsigned_tx.output.push(recipient);
+
+        let mut input = psbt::Input {
+            witness_utxo: Some(witness_utxo),
+            ..Default::default()
+        };
+        plan.update_psbt_input(&mut input);
+        psbt.inputs.push(input);
+        psbt.outputs.push(psbt::Output::default());
+
+        Ok(psbt)
+    }
+
+    /// Build a ready-to-sign PSBT letting whoever holds `refund_key`
+    /// unilaterally reclaim `previous_output` (with `witness_utxo`) to
+    /// `recipient_script` once `refund_timelock` matures, paying `fee_rate`.
+    ///
+    /// For [`UnvaultTimelock::Absolute`] the returned PSBT's `lock_time` is
+    /// set to the target height, and for [`UnvaultTimelock::Relative`] the
+    /// input's `sequence` is set to the relative delay, so the finalized
+    /// transaction can't be mined until the timelock has matured.
+    /// `finalize_mut` checks only those transaction fields, not the chain
+    /// height or the `previous_output`'s confirmations: it succeeds as soon
+    /// as the refund key has signed.
+    pub fn build_refund(
+        &self,
+        previous_output: OutPoint,
+        witness_utxo: TxOut,
+        recipient_script: ScriptBuf,
+        fee_rate: FeeRate,
+    ) -> Result<Psbt, VaultError> {
+        let (assets, lock_time, sequence) = match self.refund_timelock {
+            UnvaultTimelock::Absolute(height) => {
+                let lock_time =
+                    absolute::LockTime::from_height(height).expect("refund height fits a locktime");
+                let assets = Assets::new().add(self.refund_key.clone()).after(lock_time);
+                (assets, lock_time, Sequence::ENABLE_RBF_NO_LOCKTIME)
+            }
+            UnvaultTimelock::Relative(sequence) => {
+                let locktime = relative::LockTime::from_sequence(sequence)
+                    .expect("UnvaultTimelock::Relative always encodes a BIP68 relative locktime");
+                let assets = Assets::new().add(self.refund_key.clone()).older(locktime);
+                (assets, absolute::LockTime::ZERO, sequence)
+            }
+        };
+        let plan = self.plan_for(&assets)?;
+
+        let recipient = output_value_for_fee_rate(
+            witness_utxo.value,
+            previous_output,
+            sequence,
+            recipient_script,
+            &plan,
+            fee_rate,
+        )?;
+
+        let tx = blank_transaction(lock_time);
+        let mut psbt = Psbt::from_unsigned_tx(tx).expect("tx has no signatures yet");
+
+        psbt.unsigned_tx.input.push(TxIn {
+            previous_output,
+            sequence,
+            ..Default::default()
+        });
+        psbt.unsigned_tx.output.push(recipient);
+
+        let mut input = psbt::Input {
+            witness_utxo: Some(witness_utxo),
+            ..Default::default()
+        };
+        plan.update_psbt_input(&mut input);
+        psbt.inputs.push(input);
+        psbt.outputs.push(psbt::Output::default());
+
+        Ok(psbt)
+    }
+}
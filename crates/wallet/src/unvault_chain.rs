@@ -0,0 +1,267 @@
+// Bitcoin Dev Kit
+// Written in 2020 by Alekos Filini <alekos.filini@gmail.com>
+//
+// Copyright (c) 2020-2024 Bitcoin Dev Kit Developers
+//
+// This file is licensed under the Apache License, Version 2.0 <LICENSE-APACHE
+// or http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your option.
+// You may not use this file except in accordance with one or both of these
+// licenses.
+
+//! A two-stage unvault → cancel transaction chain, modeled as a chain of
+//! linked transactions rather than a single spend.
+//!
+//! [`UnvaultChain::build_unvault`] spends a [`Vault`](crate::vault::Vault)'s
+//! deposit into an unvault main output (spendable by the unvault key once a
+//! relative CSV delay matures, or immediately by a cancel key) plus a tiny
+//! fixed-value anchor output that lets the chain be fee-bumped with CPFP.
+//! [`UnvaultChain::build_cancel`] spends the main output of an in-flight
+//! unvault transaction back out, letting a watcher revert it before the delay
+//! expires.
+
+use std::str::FromStr;
+
+use bitcoin::{
+    absolute, psbt, transaction, Amount, FeeRate, OutPoint, Psbt, ScriptBuf, Sequence, Transaction,
+    TxIn, TxOut, Weight,
+};
+
+use miniscript::plan::Assets;
+use miniscript::policy::Concrete;
+use miniscript::{Descriptor, DescriptorPublicKey};
+
+use crate::vault::{
+    blank_transaction, output_value_for_fee_rate, UnvaultTimelock, Vault, VaultError,
+    SEGWIT_MARKER_FLAG_WEIGHT,
+};
+
+/// The value of the CPFP anchor output created by [`UnvaultChain::build_unvault`].
+pub const ANCHOR_VALUE: Amount = Amount::from_sat(330);
+
+/// The unvault main output descriptor and its CPFP anchor sibling, plus the
+/// keys needed to build the unvault and cancel transactions.
+///
+/// The main output's policy is `or(and(pk(unvault),older(csv)),pk(cancel))`:
+/// the unvault key can spend it once `csv_delay` blocks have passed since the
+/// unvault transaction confirmed, but the cancel key can spend it at any
+/// time before that, reverting the in-flight unvault.
+#[derive(Debug, Clone)]
+pub struct UnvaultChain {
+    cancel_key: DescriptorPublicKey,
+    csv_delay: Sequence,
+    unvault_descriptor: Descriptor<DescriptorPublicKey>,
+    cpfp_descriptor: Descriptor<DescriptorPublicKey>,
+}
+
+impl UnvaultChain {
+    /// Build the unvault main output descriptor (`unvault` key gated by
+    /// `csv_delay`, `cancel` key with no timelock) and a single-key `wpkh`
+    /// CPFP anchor descriptor paying `cpfp_key`.
+    pub fn new(
+        unvault_key: DescriptorPublicKey,
+        cancel_key: DescriptorPublicKey,
+        csv_delay: Sequence,
+        cpfp_key: DescriptorPublicKey,
+    ) -> Result<Self, VaultError> {
+        let policy_str = format!(
+            "or(and(pk({unvault_key}),older({})),pk({cancel_key}))",
+            csv_delay.to_consensus_u32()
+        );
+        let policy = Concrete::<DescriptorPublicKey>::from_str(&policy_str)
+            .map_err(VaultError::PolicyParse)?;
+        let compiled = policy.compile().map_err(VaultError::Policy)?;
+        let unvault_descriptor = Descriptor::new_wsh(compiled).map_err(VaultError::Descriptor)?;
+        let cpfp_descriptor = Descriptor::new_wpkh(cpfp_key).map_err(VaultError::Descriptor)?;
+
+        Ok(Self {
+            cancel_key,
+            csv_delay,
+            unvault_descriptor,
+            cpfp_descriptor,
+        })
+    }
+
+    /// The unvault main output descriptor.
+    pub fn unvault_descriptor(&self) -> &Descriptor<DescriptorPublicKey> {
+        &self.unvault_descriptor
+    }
+
+    /// The CPFP anchor output descriptor.
+    pub fn cpfp_descriptor(&self) -> &Descriptor<DescriptorPublicKey> {
+        &self.cpfp_descriptor
+    }
+
+    /// The relative CSV delay the unvault key must wait out.
+    pub fn csv_delay(&self) -> Sequence {
+        self.csv_delay
+    }
+
+    /// Spend `vault`'s deposit output into an unvault main output plus a
+    /// [`ANCHOR_VALUE`]-sat CPFP anchor output, paying `fee_rate`.
+    ///
+    /// `deposit_previous_output`/`deposit_witness_utxo` identify the vault's
+    /// deposit output, and are planned and finalized via `vault`'s unvault
+    /// path, exactly like [`Vault::spend_unvault`](crate::vault::Vault::spend_unvault).
+    ///
+    /// `csv_delay` belongs only on the chain's own `unvault_descriptor`
+    /// output, so this deposit-spending transaction must itself be
+    /// broadcastable immediately: `vault` must have a
+    /// [`UnvaultTimelock::Relative`] unvault path, or this returns
+    /// [`VaultError::AbsoluteVaultTimelock`]. An absolute vault timelock
+    /// would stamp this transaction's `lock_time` with a fixed future
+    /// height, leaving a watcher nothing to cancel until that height passed.
+    pub fn build_unvault(
+        &self,
+        vault: &Vault,
+        deposit_previous_output: OutPoint,
+        deposit_witness_utxo: TxOut,
+        fee_rate: FeeRate,
+    ) -> Result<Psbt, VaultError> {
+        if matches!(vault.timelock(), UnvaultTimelock::Absolute(_)) {
+            return Err(VaultError::AbsoluteVaultTimelock);
+        }
+
+        let (plan, lock_time, sequence) = vault.plan_unvault()?;
+
+        let unvault_spk = self
+            .unvault_descriptor
+            .at_derivation_index(0)
+            .expect("unvault descriptor has no wildcard, index is unused")
+            .script_pubkey();
+        let cpfp_spk = self
+            .cpfp_descriptor
+            .at_derivation_index(0)
+            .expect("cpfp descriptor has no wildcard, index is unused")
+            .script_pubkey();
+
+        let probe_tx = Transaction {
+            version: transaction::Version::TWO,
+            lock_time,
+            input: vec![TxIn {
+                previous_output: deposit_previous_output,
+                sequence,
+                ..Default::default()
+            }],
+            output: vec![
+                TxOut {
+                    script_pubkey: unvault_spk.clone(),
+                    value: Amount::ZERO,
+                },
+                TxOut {
+                    script_pubkey: cpfp_spk.clone(),
+                    value: ANCHOR_VALUE,
+                },
+            ],
+        };
+        let plan_weight = Weight::from_wu(plan.satisfaction_weight() as u64);
+        let total_weight = probe_tx.weight() + SEGWIT_MARKER_FLAG_WEIGHT + plan_weight;
+        let fee = fee_rate
+            .fee_vb(total_weight.to_vbytes_ceil())
+            .ok_or(VaultError::FeeExceedsInput)?;
+        let main_value = deposit_witness_utxo
+            .value
+            .checked_sub(ANCHOR_VALUE)
+            .and_then(|v| v.checked_sub(fee))
+            .ok_or(VaultError::FeeExceedsInput)?;
+        if main_value < crate::vault::DUST_LIMIT {
+            return Err(VaultError::OutputBelowDustLimit);
+        }
+
+        let tx = blank_transaction(lock_time);
+        let mut psbt = Psbt::from_unsigned_tx(tx).expect("tx has no signatures yet");
+
+        psbt.unsigned_tx.input = probe_tx.input;
+        psbt.unsigned_tx.output = vec![
+            TxOut {
+                script_pubkey: unvault_spk,
+                value: main_value,
+            },
+            TxOut {
+                script_pubkey: cpfp_spk,
+                value: ANCHOR_VALUE,
+            },
+        ];
+
+        let mut input = psbt::Input {
+            witness_utxo: Some(deposit_witness_utxo),
+            ..Default::default()
+        };
+        plan.update_psbt_input(&mut input);
+        psbt.inputs.push(input);
+        psbt.outputs.push(psbt::Output::default());
+        psbt.outputs.push(psbt::Output::default());
+
+        Ok(psbt)
+    }
+
+    /// Spend `unvault_tx`'s main output (not its CPFP anchor) back to
+    /// `recipient_script` via the cancel key, reverting the in-flight unvault.
+    ///
+    /// The input's sequence is [`Sequence::ENABLE_RBF_NO_LOCKTIME`]: BIP68
+    /// applies to any non-disabled sequence on a version-2 transaction
+    /// regardless of whether the spent script itself has a timelock, so a
+    /// `csv_delay`-valued sequence here would force the cancel to wait out
+    /// the very delay it exists to preempt. Using a disabled sequence lets
+    /// the cancel key spend immediately, as the policy intends.
+    pub fn build_cancel(
+        &self,
+        unvault_tx: &Transaction,
+        recipient_script: ScriptBuf,
+        fee_rate: FeeRate,
+    ) -> Result<Psbt, VaultError> {
+        let main_spk = self
+            .unvault_descriptor
+            .at_derivation_index(0)
+            .expect("unvault descriptor has no wildcard, index is unused")
+            .script_pubkey();
+        let (previous_output, witness_utxo) = find_vout(unvault_tx, &main_spk)?;
+
+        let assets = Assets::new().add(self.cancel_key.clone());
+        let plan = self
+            .unvault_descriptor
+            .at_derivation_index(0)
+            .expect("unvault descriptor has no wildcard, index is unused")
+            .plan(&assets)
+            .map_err(|_| VaultError::Plan)?;
+
+        let sequence = Sequence::ENABLE_RBF_NO_LOCKTIME;
+        let recipient = output_value_for_fee_rate(
+            witness_utxo.value,
+            previous_output,
+            sequence,
+            recipient_script,
+            &plan,
+            fee_rate,
+        )?;
+
+        let tx = blank_transaction(absolute::LockTime::ZERO);
+        let mut psbt = Psbt::from_unsigned_tx(tx).expect("tx has no signatures yet");
+
+        psbt.unsigned_tx.input.push(TxIn {
+            previous_output,
+            sequence,
+            ..Default::default()
+        });
+        psbt.unsigned_tx.output.push(recipient);
+
+        let mut input = psbt::Input {
+            witness_utxo: Some(witness_utxo),
+            ..Default::default()
+        };
+        plan.update_psbt_input(&mut input);
+        psbt.inputs.push(input);
+        psbt.outputs.push(psbt::Output::default());
+
+        Ok(psbt)
+    }
+}
+
+fn find_vout(tx: &Transaction, spk: &ScriptBuf) -> Result<(OutPoint, TxOut), VaultError> {
+    for (i, txout) in tx.output.iter().enumerate() {
+        if &txout.script_pubkey == spk {
+            return Ok((OutPoint::new(tx.compute_txid(), i as u32), txout.clone()));
+        }
+    }
+    Err(VaultError::OutputNotFound)
+}
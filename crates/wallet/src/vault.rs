@@ -0,0 +1,429 @@
+// Bitcoin Dev Kit
+// Written in 2020 by Alekos Filini <alekos.filini@gmail.com>
+//
+// Copyright (c) 2020-2024 Bitcoin Dev Kit Developers
+//
+// This file is licensed under the Apache License, Version 2.0 <LICENSE-APACHE
+// or http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your option.
+// You may not use this file except in accordance with one or both of these
+// licenses.
+
+//! A two-path "vault" descriptor: spend at any time with an emergency key, or
+//! spend after a timelock with an unvault key.
+//!
+//! This wraps the `or(pk(emergency),and(pk(unvault),after(h)))` (or,
+//! equivalently, `older(h)` for a relative timelock) miniscript policy
+//! described in the `descriptor_with_plan` example behind a small API so that
+//! callers can't forget to feed the satisfier the assets for the path they
+//! intend to spend.
+
+use core::fmt;
+use core::str::FromStr;
+
+use bitcoin::psbt::PsbtParseError;
+use bitcoin::secp256k1::{Secp256k1, Verification};
+use bitcoin::{
+    absolute, psbt, relative, transaction, Address, Amount, FeeRate, Network, OutPoint, Psbt,
+    ScriptBuf, Sequence, TxIn, TxOut, Weight,
+};
+
+use miniscript::plan::{Assets, Plan};
+use miniscript::policy::Concrete;
+use miniscript::psbt::PsbtExt;
+use miniscript::{Descriptor, DescriptorPublicKey};
+
+/// Errors that can occur while building or spending a [`Vault`].
+#[derive(Debug)]
+pub enum VaultError {
+    /// A concrete policy (the vault's, a shared output's or an unvault
+    /// chain's) failed to compile.
+    Policy(miniscript::policy::compiler::CompilerError),
+    /// A policy string built from descriptor keys failed to parse as a
+    /// concrete policy.
+    PolicyParse(miniscript::Error),
+    /// A `wsh` descriptor could not be built from a compiled policy, or a
+    /// `wpkh` descriptor from a key.
+    Descriptor(miniscript::Error),
+    /// No plan could be found for the requested spend path with the given assets.
+    Plan,
+    /// `fee_rate * weight` overflowed, or the deposit is too small to pay that fee.
+    FeeExceedsInput,
+    /// The output left after paying the fee would be below the P2WSH dust limit.
+    OutputBelowDustLimit,
+    /// A BIP174 base64-encoded PSBT failed to parse.
+    PsbtParse(PsbtParseError),
+    /// The watch-only PSBT and the offline-signed PSBT could not be combined,
+    /// e.g. because they don't describe the same unsigned transaction.
+    Combine(psbt::Error),
+    /// The combined PSBT did not satisfy the vault descriptor and could not be finalized.
+    Finalize,
+    /// A transaction in a chain did not contain the expected output.
+    OutputNotFound,
+    /// [`UnvaultChain::build_unvault`](crate::unvault_chain::UnvaultChain::build_unvault)
+    /// was asked to spend a vault whose own unvault path is gated by an
+    /// [`UnvaultTimelock::Absolute`] height: the deposit-spending transaction
+    /// would inherit that height as its `lock_time` and couldn't be mined
+    /// until it passed, defeating the chain's "unvault now, `csv_delay`
+    /// before it's final" design. Use a [`UnvaultTimelock::Relative`] vault
+    /// instead.
+    AbsoluteVaultTimelock,
+    /// An [`UnvaultTimelock::Absolute`] height does not fit a transaction
+    /// `lock_time` (it must be below the 500,000,000 block/time-unit switch
+    /// threshold).
+    InvalidTimelockHeight(absolute::ConversionError),
+}
+
+impl fmt::Display for VaultError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            VaultError::Policy(e) => write!(f, "vault policy failed to compile: {e}"),
+            VaultError::PolicyParse(e) => write!(f, "vault policy string failed to parse: {e}"),
+            VaultError::Descriptor(e) => write!(f, "vault descriptor could not be built: {e}"),
+            VaultError::Plan => write!(f, "no plan exists for the requested vault spend path"),
+            VaultError::FeeExceedsInput => {
+                write!(f, "the fee for the requested fee rate exceeds the input value")
+            }
+            VaultError::OutputBelowDustLimit => write!(
+                f,
+                "the output value after paying the fee is below the P2WSH dust limit ({DUST_LIMIT})"
+            ),
+            VaultError::PsbtParse(e) => write!(f, "couldn't parse PSBT from base64: {e}"),
+            VaultError::Combine(e) => write!(f, "couldn't combine watch-only and signed PSBTs: {e}"),
+            VaultError::Finalize => write!(f, "combined PSBT does not satisfy the vault descriptor"),
+            VaultError::OutputNotFound => {
+                write!(f, "expected output was not found in the given transaction")
+            }
+            VaultError::AbsoluteVaultTimelock => write!(
+                f,
+                "unvault chains require a vault with a relative (not absolute) unvault timelock"
+            ),
+            VaultError::InvalidTimelockHeight(e) => {
+                write!(f, "invalid absolute timelock height: {e}")
+            }
+        }
+    }
+}
+
+impl std::error::Error for VaultError {}
+
+/// The dust limit for a P2WSH output (330 sats under Bitcoin Core's default
+/// dust relay fee of 3 sat/vB).
+pub const DUST_LIMIT: Amount = Amount::from_sat(330);
+
+/// The timelock that gates the unvault spend path of a [`Vault`].
+///
+/// `Absolute` matches the `after(h)` miniscript fragment: the unvault key
+/// becomes usable once the chain reaches block height `h`. `Relative` matches
+/// `older(n)`: the unvault key becomes usable `n` blocks after the deposit
+/// transaction that created the vault output confirms, per BIP68/CSV.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum UnvaultTimelock {
+    /// Spendable once the chain reaches this block height.
+    Absolute(u32),
+    /// Spendable this many blocks after the deposit transaction confirms.
+    Relative(Sequence),
+}
+
+impl fmt::Display for UnvaultTimelock {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            UnvaultTimelock::Absolute(height) => write!(f, "after({height})"),
+            UnvaultTimelock::Relative(sequence) => {
+                write!(f, "older({})", sequence.to_consensus_u32())
+            }
+        }
+    }
+}
+
+/// A vault with an emergency spend path and a timelocked unvault spend path.
+///
+/// Construct one with [`Vault::new`], send funds to [`Vault::deposit_address`],
+/// then use [`Vault::spend_emergency`] or [`Vault::spend_unvault`] to produce a
+/// ready-to-sign [`Psbt`] for the path you want.
+#[derive(Debug, Clone)]
+pub struct Vault {
+    emergency_key: DescriptorPublicKey,
+    unvault_key: DescriptorPublicKey,
+    timelock: UnvaultTimelock,
+    descriptor: Descriptor<DescriptorPublicKey>,
+}
+
+impl Vault {
+    /// Build a vault from an `emergency` key that can spend at any time, and
+    /// an `unvault` key that can spend only once `timelock` has matured, and
+    /// wrap the resulting policy in a `wsh` descriptor.
+    pub fn new(
+        emergency: DescriptorPublicKey,
+        unvault: DescriptorPublicKey,
+        timelock: UnvaultTimelock,
+    ) -> Result<Self, VaultError> {
+        if let UnvaultTimelock::Absolute(height) = timelock {
+            absolute::LockTime::from_height(height).map_err(VaultError::InvalidTimelockHeight)?;
+        }
+
+        let policy_str = format!("or(pk({emergency}),and(pk({unvault}),{timelock}))");
+        let policy = Concrete::<DescriptorPublicKey>::from_str(&policy_str)
+            .map_err(VaultError::PolicyParse)?;
+        let compiled = policy.compile().map_err(VaultError::Policy)?;
+        let descriptor = Descriptor::new_wsh(compiled).map_err(VaultError::Descriptor)?;
+
+        Ok(Self {
+            emergency_key: emergency,
+            unvault_key: unvault,
+            timelock,
+            descriptor,
+        })
+    }
+
+    /// The underlying `wsh(or(pk(emergency),and(pk(unvault),...)))` descriptor.
+    pub fn descriptor(&self) -> &Descriptor<DescriptorPublicKey> {
+        &self.descriptor
+    }
+
+    /// The timelock that gates the unvault spend path.
+    pub fn timelock(&self) -> UnvaultTimelock {
+        self.timelock
+    }
+
+    /// The address funds should be deposited to.
+    pub fn deposit_address(&self, network: Network) -> Address {
+        self.descriptor
+            .at_derivation_index(0)
+            .expect("vault descriptor has no wildcard, index is unused")
+            .address(network)
+            .expect("wsh descriptors always have an address")
+    }
+
+    fn plan_for(&self, assets: &Assets) -> Result<Plan, VaultError> {
+        self.descriptor
+            .at_derivation_index(0)
+            .expect("vault descriptor has no wildcard, index is unused")
+            .plan(assets)
+            .map_err(|_| VaultError::Plan)
+    }
+
+    /// The plan satisfying the unvault path, and the `(lock_time, sequence)`
+    /// the spending transaction must carry so the
+    /// timelock is consensus-enforced. Shared by [`Vault::spend_unvault`] and
+    /// the [`unvault_chain`](crate::unvault_chain) module, which spends the
+    /// same deposit via the same path on its way into a two-stage chain.
+    pub(crate) fn plan_unvault(&self) -> Result<(Plan, absolute::LockTime, Sequence), VaultError> {
+        let (assets, lock_time, sequence) = match self.timelock {
+            UnvaultTimelock::Absolute(height) => {
+                let lock_time = absolute::LockTime::from_height(height)
+                    .expect("height was validated by Vault::new");
+                let assets = Assets::new()
+                    .add(self.unvault_key.clone())
+                    .after(lock_time);
+                // The absolute timelock lives in the tx `lock_time`, so the
+                // sequence just needs to stay non-final to let it take effect.
+                (assets, lock_time, Sequence::ENABLE_RBF_NO_LOCKTIME)
+            }
+            UnvaultTimelock::Relative(sequence) => {
+                let locktime = relative::LockTime::from_sequence(sequence)
+                    .expect("UnvaultTimelock::Relative always encodes a BIP68 relative locktime");
+                let assets = Assets::new().add(self.unvault_key.clone()).older(locktime);
+                (assets, absolute::LockTime::ZERO, sequence)
+            }
+        };
+        let plan = self.plan_for(&assets)?;
+        Ok((plan, lock_time, sequence))
+    }
+
+    /// Build a ready-to-sign PSBT spending `previous_output` (with `witness_utxo`)
+    /// via the emergency path to `recipient_script`, paying `fee_rate`.
+    ///
+    /// The output value is `witness_utxo.value` minus the fee implied by
+    /// `fee_rate` and the plan's predicted satisfaction weight; see
+    /// [`output_value_for_fee_rate`].
+    pub fn spend_emergency(
+        &self,
+        previous_output: OutPoint,
+        witness_utxo: TxOut,
+        recipient_script: ScriptBuf,
+        fee_rate: FeeRate,
+    ) -> Result<Psbt, VaultError> {
+        let assets = Assets::new().add(self.emergency_key.clone());
+        let plan = self.plan_for(&assets)?;
+        let sequence = Sequence::ENABLE_RBF_NO_LOCKTIME;
+
+        let recipient = output_value_for_fee_rate(
+            witness_utxo.value,
+            previous_output,
+            sequence,
+            recipient_script,
+            &plan,
+            fee_rate,
+        )?;
+
+        let tx = blank_transaction(absolute::LockTime::ZERO);
+        let mut psbt = Psbt::from_unsigned_tx(tx).expect("tx has no signatures yet");
+
+        psbt.unsigned_tx.input.push(TxIn {
+            previous_output,
+            sequence,
+            ..Default::default()
+        });
+        psbt.unsigned_tx.output.push(recipient);
+
+        let mut input = psbt::Input {
+            witness_utxo: Some(witness_utxo),
+            ..Default::default()
+        };
+        plan.update_psbt_input(&mut input);
+        psbt.inputs.push(input);
+        psbt.outputs.push(psbt::Output::default());
+
+        Ok(psbt)
+    }
+
+    /// Build a ready-to-sign PSBT spending `previous_output` (with `witness_utxo`)
+    /// via the unvault path to `recipient_script`, paying `fee_rate`.
+    ///
+    /// A relative ([`UnvaultTimelock::Relative`]) unvault path requires a
+    /// non-final input `sequence` and a version-2 transaction to be
+    /// consensus-enforced by BIP68/CSV: [`blank_transaction`] always builds a
+    /// version-2 transaction, and this method sets the matching sequence
+    /// below, so finalizing the returned PSBT never silently produces an
+    /// unspendable transaction. The output value is sized the same way as in
+    /// [`Vault::spend_emergency`]; see [`output_value_for_fee_rate`].
+    pub fn spend_unvault(
+        &self,
+        previous_output: OutPoint,
+        witness_utxo: TxOut,
+        recipient_script: ScriptBuf,
+        fee_rate: FeeRate,
+    ) -> Result<Psbt, VaultError> {
+        let (plan, lock_time, sequence) = self.plan_unvault()?;
+
+        let recipient = output_value_for_fee_rate(
+            witness_utxo.value,
+            previous_output,
+            sequence,
+            recipient_script,
+            &plan,
+            fee_rate,
+        )?;
+
+        let tx = blank_transaction(lock_time);
+        let mut psbt = Psbt::from_unsigned_tx(tx).expect("tx has no signatures yet");
+
+        psbt.unsigned_tx.input.push(TxIn {
+            previous_output,
+            sequence,
+            ..Default::default()
+        });
+        psbt.unsigned_tx.output.push(recipient);
+
+        let mut input = psbt::Input {
+            witness_utxo: Some(witness_utxo),
+            ..Default::default()
+        };
+        plan.update_psbt_input(&mut input);
+        psbt.inputs.push(input);
+        psbt.outputs.push(psbt::Output::default());
+
+        Ok(psbt)
+    }
+}
+
+/// The weight of the segwit marker and flag bytes (`0x00 0x01`), counted as
+/// witness data (1 weight unit per byte, unlike the 4x-weighted base size).
+///
+/// [`bitcoin::Transaction::weight`] omits these when every input's witness is
+/// empty, serializing as a legacy (non-segwit) transaction — which is always
+/// true of the unsigned, witness-less probe transaction built below, even
+/// though the finalized transaction carries a witness and so does include
+/// them.
+pub(crate) const SEGWIT_MARKER_FLAG_WEIGHT: Weight = Weight::from_wu(2);
+
+/// Size a single-input, single-output spend's output value so that it pays
+/// `fee_rate` given the plan's predicted satisfaction (witness) weight.
+///
+/// The total weight is the weight of the unsigned, witness-less transaction
+/// (version, locktime, input outpoint+sequence, output scriptpubkey+value)
+/// plus the segwit marker/flag plus `plan`'s satisfaction weight. Returns
+/// [`VaultError::OutputBelowDustLimit`] if the resulting output would be
+/// below [`DUST_LIMIT`].
+pub fn output_value_for_fee_rate(
+    input_value: Amount,
+    previous_output: OutPoint,
+    sequence: Sequence,
+    recipient_script: ScriptBuf,
+    plan: &Plan,
+    fee_rate: FeeRate,
+) -> Result<TxOut, VaultError> {
+    let unsigned_tx = bitcoin::Transaction {
+        version: transaction::Version::TWO,
+        lock_time: absolute::LockTime::ZERO,
+        input: vec![TxIn {
+            previous_output,
+            sequence,
+            ..Default::default()
+        }],
+        output: vec![TxOut {
+            script_pubkey: recipient_script.clone(),
+            value: Amount::ZERO,
+        }],
+    };
+    let plan_weight = Weight::from_wu(plan.satisfaction_weight() as u64);
+    let total_weight = unsigned_tx.weight() + SEGWIT_MARKER_FLAG_WEIGHT + plan_weight;
+
+    let fee = fee_rate
+        .fee_vb(total_weight.to_vbytes_ceil())
+        .ok_or(VaultError::FeeExceedsInput)?;
+    let value = input_value
+        .checked_sub(fee)
+        .ok_or(VaultError::FeeExceedsInput)?;
+    if value < DUST_LIMIT {
+        return Err(VaultError::OutputBelowDustLimit);
+    }
+
+    Ok(TxOut {
+        script_pubkey: recipient_script,
+        value,
+    })
+}
+
+/// Serialize `psbt` to its BIP174 base64 text encoding, e.g. to hand a
+/// watch-only side's unsigned spend PSBT to an offline signer.
+///
+/// `Psbt`'s `Display`/`FromStr` base64 impls require the `bitcoin` crate's
+/// `base64` feature; this crate's manifest must enable it (directly or via
+/// another dependency) for `to_base64`/`from_base64` to compile.
+pub fn to_base64(psbt: &Psbt) -> String {
+    psbt.to_string()
+}
+
+/// Parse a PSBT from its BIP174 base64 text encoding.
+pub fn from_base64(s: &str) -> Result<Psbt, VaultError> {
+    Psbt::from_str(s).map_err(VaultError::PsbtParse)
+}
+
+/// Combine the watch-only side's unsigned `psbt` with a `signed` copy of the
+/// same PSBT produced by an offline signer, then finalize it.
+///
+/// `update_psbt_input` (called by [`Vault::spend_emergency`] and
+/// [`Vault::spend_unvault`]) embeds the witness script and the derivation
+/// hints for the required keys directly in the PSBT, so the offline signer
+/// only needs an [`Xpriv`](bitcoin::bip32::Xpriv) to sign it — it does not
+/// need to know the vault's policy or descriptor.
+pub fn combine_and_finalize<C: Verification>(
+    mut psbt: Psbt,
+    signed: Psbt,
+    secp: &Secp256k1<C>,
+) -> Result<Psbt, VaultError> {
+    psbt.combine(signed).map_err(VaultError::Combine)?;
+    psbt.finalize_mut(secp).map_err(|_| VaultError::Finalize)?;
+    Ok(psbt)
+}
+
+pub(crate) fn blank_transaction(lock_time: absolute::LockTime) -> bitcoin::Transaction {
+    bitcoin::Transaction {
+        version: transaction::Version::TWO,
+        lock_time,
+        input: vec![],
+        output: vec![],
+    }
+}